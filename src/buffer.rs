@@ -1,12 +1,18 @@
-use std::{cmp, collections::BTreeSet, fmt::Display};
+use std::{cmp, fmt::Display};
 
 use crate::text::{Position, TextNode};
 
+mod rope;
+
+use rope::Rope;
+
 /// A heap-allocated buffer designed for efficient insertion, deletion and edit operations on containing [TextNode] values.
+/// Backed by a [Rope] rather than a flat collection, so edits near the start of a large document
+/// only rewrite the handful of chunks they actually touch instead of the whole buffer.
 #[derive(Debug)]
 pub(crate) struct TextBuffer {
-    // The containing nodes for this buffer making up the entire text it has governance over.
-    nodes: BTreeSet<TextNode>,
+    // The rope of text chunks making up the entire text this buffer has governance over.
+    rope: Rope,
 
     // The virtual boundary this buffer takes up. For example, there could be multiple buffers pooled for a single document
     // with each taking up a certain amount of space depending on the total amount of containing nodes.
@@ -17,7 +23,7 @@ impl TextBuffer {
     /// Constructs a new [TextBuffer] from the provided string value.
     pub fn new() -> Self {
         Self {
-            nodes: BTreeSet::new(),
+            rope: Rope::new(),
             boundary: Boundary::default(),
         }
     }
@@ -25,25 +31,38 @@ impl TextBuffer {
     /// Pushes a [TextNode] into this buffer, adjusting the boundary if needed.
     pub fn push(&mut self, node: TextNode) {
         self.boundary = self.boundary.union(node.dimensions);
-        self.nodes.insert(node);
+        self.rope.push(node);
     }
 
+    /// Applies a [Transaction] to this buffer, rewriting only the rope subtrees the transaction's
+    /// changes actually touch and recomputing their dimensions, then refreshing the buffer's
+    /// overall [Boundary] from the rope's cached metrics.
+    pub fn apply(&mut self, transaction: &Transaction) {
+        self.rope.apply(transaction);
+        self.boundary = rope::boundary_from_metrics(self.rope.metrics());
+    }
 
+    /// The buffer's entire text, as UTF-16 code points, concatenated in document order.
+    fn flatten(&self) -> Vec<u16> {
+        self.rope.flatten()
+    }
 }
 
 #[doc(hidden)]
 impl Display for TextBuffer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for node in self.nodes.iter() {
+        self.rope.for_each_leaf(&mut |node| {
             for line in node.lines() {
                 let _ = f.write_str(&line);
             }
-        }
+        });
         Ok(())
     }
 }
 
 /// A virtual bounding box comprised of a position, denoting its origin, as well as a width & height value used to calculate its span.
+/// `width` is measured in on-screen terminal cells (see [`TextNode::visual_width`](crate::text::TextNode::visual_width)), not code
+/// units, so it lines up with a terminal grid.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Boundary {
     pub(crate) origin: Position,
@@ -82,3 +101,322 @@ impl Boundary {
         &self.origin
     }
 }
+
+/// A single operation within a [Transaction], measured in positions (UTF-16 code points) over
+/// the whole buffer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Change {
+    /// Leaves `n` positions of existing content untouched.
+    Retain(usize),
+    /// Removes `n` positions of existing content.
+    Delete(usize),
+    /// Inserts the given code points at the current position.
+    Insert(Vec<u16>),
+}
+
+/// An ordered, contiguous list of [Change]s describing an edit to a [TextBuffer], built by
+/// appending retains, deletes and inserts in document order. Passing one to [`TextBuffer::apply`]
+/// performs the edit; [`Transaction::invert`] produces its undo, and [`Transaction::compose`]
+/// merges two sequential transactions into one.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Transaction {
+    changes: Vec<Change>,
+}
+
+impl Transaction {
+    /// Constructs an empty transaction.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a retain of `n` positions. A no-op for `n == 0`.
+    pub fn retain(mut self, n: usize) -> Self {
+        if n > 0 {
+            self.changes.push(Change::Retain(n));
+        }
+        self
+    }
+
+    /// Appends a delete of `n` positions. A no-op for `n == 0`.
+    pub fn delete(mut self, n: usize) -> Self {
+        if n > 0 {
+            self.changes.push(Change::Delete(n));
+        }
+        self
+    }
+
+    /// Appends an insert of the given code points. A no-op for an empty insert.
+    pub fn insert(mut self, code_points: impl Into<Vec<u16>>) -> Self {
+        let code_points = code_points.into();
+        if !code_points.is_empty() {
+            self.changes.push(Change::Insert(code_points));
+        }
+        self
+    }
+
+    /// Merges `self` and `other`, two transactions meant to be applied one after the other, into
+    /// a single transaction with the same net effect. Advances through both op lists in lockstep:
+    /// inserts introduced by `other` pass straight through, deletes from `self` pass straight
+    /// through (the content they remove never reaches `other`'s document), and everything else is
+    /// co-iterated, splitting ops at whichever boundary comes first so a delete in `other` can
+    /// cancel an insert from `self`.
+    pub fn compose(self, other: Self) -> Self {
+        let mut a_iter = self.changes.into_iter();
+        let mut b_iter = other.changes.into_iter();
+        let mut a = a_iter.next();
+        let mut b = b_iter.next();
+        let mut result: Vec<Change> = Vec::new();
+
+        loop {
+            match (a, b) {
+                (None, None) => break,
+                (a_op, Some(Change::Insert(text))) => {
+                    push_change(&mut result, Change::Insert(text));
+                    a = a_op;
+                    b = b_iter.next();
+                }
+                (Some(Change::Delete(n)), b_op) => {
+                    push_change(&mut result, Change::Delete(n));
+                    a = a_iter.next();
+                    b = b_op;
+                }
+                (Some(Change::Insert(mut text)), Some(b_op)) => {
+                    let len = text.len();
+                    let (taken, remainder) = split_change(b_op, len);
+                    let taken_len = match taken {
+                        Taken::Retain(n) => n,
+                        Taken::Delete(n) => n,
+                    };
+                    let rest = text.split_off(taken_len);
+                    if let Taken::Retain(_) = taken {
+                        push_change(&mut result, Change::Insert(text));
+                    }
+                    a = if rest.is_empty() {
+                        a_iter.next()
+                    } else {
+                        Some(Change::Insert(rest))
+                    };
+                    b = remainder.or_else(|| b_iter.next());
+                }
+                (Some(Change::Retain(n)), Some(b_op)) => {
+                    let (taken, remainder) = split_change(b_op, n);
+                    let (consumed, change) = match taken {
+                        Taken::Retain(len) => (len, Change::Retain(len)),
+                        Taken::Delete(len) => (len, Change::Delete(len)),
+                    };
+                    push_change(&mut result, change);
+                    a = if consumed == n {
+                        a_iter.next()
+                    } else {
+                        Some(Change::Retain(n - consumed))
+                    };
+                    b = remainder.or_else(|| b_iter.next());
+                }
+                (Some(a_op), None) => {
+                    // `other` ended first, implicitly retaining the rest; whatever `self` has
+                    // left (an insert or a retain - deletes were already handled above) passes
+                    // straight through.
+                    push_change(&mut result, a_op);
+                    a = a_iter.next();
+                    b = None;
+                }
+                (None, Some(b_op)) => {
+                    // `self` ended first, implicitly retaining the rest of its document; `other`
+                    // acts directly on that untouched tail, so its op passes straight through.
+                    push_change(&mut result, b_op);
+                    a = None;
+                    b = b_iter.next();
+                }
+            }
+        }
+
+        Transaction { changes: result }
+    }
+
+    /// Produces the transaction that undoes `self`, capturing the text `self`'s deletes would
+    /// remove from `buffer` before it is applied. Retains pass through unchanged, each delete
+    /// becomes an insert of the text it would have removed, and each insert becomes a delete of
+    /// the same length.
+    pub fn invert(&self, buffer: &TextBuffer) -> Self {
+        let doc = buffer.flatten();
+        let mut pos = 0usize;
+        let mut inverse = Vec::with_capacity(self.changes.len());
+
+        for change in &self.changes {
+            match change {
+                Change::Retain(n) => {
+                    inverse.push(Change::Retain(*n));
+                    pos += n;
+                }
+                Change::Delete(n) => {
+                    inverse.push(Change::Insert(doc[pos..pos + n].to_vec()));
+                    pos += n;
+                }
+                Change::Insert(code_points) => {
+                    inverse.push(Change::Delete(code_points.len()));
+                }
+            }
+        }
+
+        Transaction { changes: inverse }
+    }
+}
+
+/// Appends `change` to `changes`, merging it into a trailing op of the same kind when possible so
+/// composed/inverted transactions stay compact.
+fn push_change(changes: &mut Vec<Change>, change: Change) {
+    match (changes.last_mut(), change) {
+        (Some(Change::Retain(prev)), Change::Retain(n)) => *prev += n,
+        (Some(Change::Delete(prev)), Change::Delete(n)) => *prev += n,
+        (Some(Change::Insert(prev)), Change::Insert(n)) => prev.extend(n),
+        (_, change) => changes.push(change),
+    }
+}
+
+/// The portion of a [Retain](Change::Retain)/[Delete](Change::Delete) op that [`Transaction::compose`]'s co-iteration consumed.
+enum Taken {
+    Retain(usize),
+    Delete(usize),
+}
+
+/// Splits `op` (a `Retain` or `Delete`) against a budget of `n` positions, returning how much of
+/// `op` was taken and, if `op` was longer than `n`, the remainder left to consume on the next
+/// pass.
+fn split_change(op: Change, n: usize) -> (Taken, Option<Change>) {
+    match op {
+        Change::Retain(m) => {
+            let taken = n.min(m);
+            let remainder = (m > taken).then_some(Change::Retain(m - taken));
+            (Taken::Retain(taken), remainder)
+        }
+        Change::Delete(m) => {
+            let taken = n.min(m);
+            let remainder = (m > taken).then_some(Change::Delete(m - taken));
+            (Taken::Delete(taken), remainder)
+        }
+        Change::Insert(_) => unreachable!("inserts from `other` are consumed before reaching split_change"),
+    }
+}
+
+/// Walks a [Transaction]'s [Change] list node-by-node as the buffer's [Rope] visits its leaf
+/// [TextNode]s, splitting ops at node boundaries so each node only sees the slice of the
+/// transaction that applies to it.
+struct ChangeCursor<'a> {
+    ops: std::slice::Iter<'a, Change>,
+    current: Option<Change>,
+}
+
+impl<'a> ChangeCursor<'a> {
+    fn new(changes: &'a [Change]) -> Self {
+        let mut ops = changes.iter();
+        let current = ops.next().cloned();
+        Self { ops, current }
+    }
+
+    /// Consumes the retain/delete positions belonging to `node`, plus any inserts landing at its
+    /// start or end, producing its new code points if a delete or insert touched it, or `None` if
+    /// the node passed through untouched (in which case it's kept as-is).
+    fn apply_to_node(&mut self, node: &TextNode) -> Option<Vec<u16>> {
+        let len = node.code_points().len();
+        let mut consumed = 0usize;
+        let mut out: Vec<u16> = Vec::new();
+        let mut touched = false;
+
+        loop {
+            match &self.current {
+                None => break,
+                Some(Change::Insert(code_points)) => {
+                    out.extend_from_slice(code_points);
+                    touched = true;
+                    self.current = self.ops.next().cloned();
+                }
+                Some(Change::Retain(n)) if consumed < len => {
+                    let take = (*n).min(len - consumed);
+                    out.extend_from_slice(&node.code_points()[consumed..consumed + take]);
+                    consumed += take;
+                    self.current = if take == *n {
+                        self.ops.next().cloned()
+                    } else {
+                        Some(Change::Retain(n - take))
+                    };
+                }
+                Some(Change::Delete(n)) if consumed < len => {
+                    let take = (*n).min(len - consumed);
+                    consumed += take;
+                    touched = true;
+                    self.current = if take == *n {
+                        self.ops.next().cloned()
+                    } else {
+                        Some(Change::Delete(n - take))
+                    };
+                }
+                _ => break,
+            }
+        }
+
+        touched.then_some(out)
+    }
+
+    /// True when the current op is a [Retain](Change::Retain) covering at least `n` positions, or
+    /// when the ops are exhausted (the rest of the buffer is implicitly retained), in which case
+    /// that many positions can be skipped over in one step instead of being walked one node at a
+    /// time.
+    fn fully_retains(&self, n: usize) -> bool {
+        match self.current {
+            None => true,
+            Some(Change::Retain(m)) => m >= n,
+            _ => false,
+        }
+    }
+
+    /// Consumes `n` positions from the current [Retain](Change::Retain) op, assumed to cover at
+    /// least that many (see [`fully_retains`](Self::fully_retains)).
+    fn skip_retain(&mut self, n: usize) {
+        self.current = match self.current.take() {
+            Some(Change::Retain(m)) if m > n => Some(Change::Retain(m - n)),
+            Some(Change::Retain(_)) => self.ops.next().cloned(),
+            other => other,
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn buffer_from(text: &str) -> TextBuffer {
+        let mut buffer = TextBuffer::new();
+        buffer.push(TextNode::new_delimitered(text));
+        buffer
+    }
+
+    #[test]
+    fn compose_matches_sequential_application() {
+        let text = "abcdef\n";
+        let first = Transaction::new().retain(2).insert("X").retain(5);
+        let second = Transaction::new().retain(3).delete(1).retain(4);
+
+        let mut sequential = buffer_from(text);
+        sequential.apply(&first);
+        sequential.apply(&second);
+
+        let mut composed = buffer_from(text);
+        composed.apply(&first.compose(second));
+
+        assert_eq!(sequential.to_string(), composed.to_string());
+    }
+
+    #[test]
+    fn invert_undoes_the_original_transaction() {
+        let text = "hello world\n";
+        let mut buffer = buffer_from(text);
+        let transaction = Transaction::new().retain(6).delete(5).insert("there").retain(1);
+
+        let inverse = transaction.invert(&buffer);
+        buffer.apply(&transaction);
+        assert_eq!(buffer.to_string(), "hello there\n");
+
+        buffer.apply(&inverse);
+        assert_eq!(buffer.to_string(), text);
+    }
+}
@@ -0,0 +1,315 @@
+use crate::{
+    buffer::{Boundary, ChangeCursor, Transaction},
+    text::{Position, TextNode},
+};
+
+/// The maximum number of UTF-16 code units a leaf is allowed to hold before it's split in two.
+/// A few KB of UTF-16 keeps leaves cheap to rebuild while still bounding the tree's depth.
+const MAX_LEAF_UNITS: usize = 2048;
+
+/// The cached aggregate size of a [RopeNode] subtree, kept up to date as edits split and merge
+/// leaves so [`Rope::metrics`] and [`TextBuffer`](crate::buffer::TextBuffer)'s boundary
+/// recomputation never need to walk every leaf.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Metrics {
+    pub units: usize,
+    pub bytes: usize,
+    pub lines: usize,
+    pub max_width: usize,
+}
+
+impl Metrics {
+    /// The metrics of a single [TextNode] leaf.
+    fn of(node: &TextNode) -> Self {
+        Self {
+            units: node.code_points().len(),
+            bytes: node.byte_len(),
+            lines: node.line_count(),
+            max_width: node.dimensions.width,
+        }
+    }
+
+    /// Combines two adjacent subtrees' metrics into their parent's.
+    fn combine(self, other: Self) -> Self {
+        Self {
+            units: self.units + other.units,
+            bytes: self.bytes + other.bytes,
+            lines: self.lines + other.lines,
+            max_width: self.max_width.max(other.max_width),
+        }
+    }
+}
+
+/// A node in the rope's balanced binary tree: either a leaf holding one [TextNode] chunk, or an
+/// internal node caching the combined [Metrics] of its two children, plus the subtree's `height`,
+/// so lookups and edits can skip whole subtrees instead of descending into every leaf, and
+/// [`join`] can keep the tree's depth logarithmic as nodes are pushed.
+#[derive(Debug)]
+enum RopeNode {
+    Leaf(TextNode),
+    Internal {
+        metrics: Metrics,
+        height: usize,
+        left: Box<RopeNode>,
+        right: Box<RopeNode>,
+    },
+}
+
+impl RopeNode {
+    /// This subtree's cached metrics, O(1) for both leaves and internal nodes.
+    fn metrics(&self) -> Metrics {
+        match self {
+            Self::Leaf(node) => Metrics::of(node),
+            Self::Internal { metrics, .. } => *metrics,
+        }
+    }
+
+    /// This subtree's height: `0` for a leaf, one more than the taller child for an internal
+    /// node. Compared by [`join`] and [`rebalance`] to decide when a subtree has tipped over
+    /// into needing a rotation.
+    fn height(&self) -> usize {
+        match self {
+            Self::Leaf(_) => 0,
+            Self::Internal { height, .. } => *height,
+        }
+    }
+
+    /// Invokes `f` with every leaf [TextNode] in this subtree, in document order.
+    fn for_each_leaf<'a>(&'a self, f: &mut impl FnMut(&'a TextNode)) {
+        match self {
+            Self::Leaf(node) => f(node),
+            Self::Internal { left, right, .. } => {
+                left.for_each_leaf(f);
+                right.for_each_leaf(f);
+            }
+        }
+    }
+}
+
+/// A balanced binary rope of [TextNode] chunks backing a [`TextBuffer`](crate::buffer::TextBuffer).
+/// Leaves hold at most [`MAX_LEAF_UNITS`] code units each, unless a single line alone is longer
+/// than that (leaves are only split between lines); internal nodes cache their subtree's
+/// combined [Metrics]. [`Rope::push`] rebalances via [`join`] on every insertion, so the tree's
+/// depth stays O(log n) in the number of leaves rather than growing into a spine, and
+/// [`Rope::apply`] uses the cached metrics to skip whole subtrees a transaction only retains, so
+/// edits cost O(log n) against the size of the document rather than rewriting it in full.
+#[derive(Debug, Default)]
+pub(crate) struct Rope {
+    root: Option<Box<RopeNode>>,
+}
+
+impl Rope {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The rope's overall size, in O(1), from the root's cached metrics.
+    pub fn metrics(&self) -> Metrics {
+        self.root.as_deref().map(RopeNode::metrics).unwrap_or_default()
+    }
+
+    /// Appends `node` as a new leaf, splitting it first if it's over [`MAX_LEAF_UNITS`], then
+    /// rebalances so the tree never degenerates into a left-leaning spine as pushes accumulate.
+    pub fn push(&mut self, node: TextNode) {
+        let added = Box::new(split_if_needed(node));
+        self.root = Some(Box::new(match self.root.take() {
+            None => *added,
+            Some(root) => join(root, added),
+        }));
+    }
+
+    /// Applies a [Transaction], descending only into the subtrees it actually touches: a subtree
+    /// the transaction's current op fully retains is skipped in O(1), untouched.
+    pub fn apply(&mut self, transaction: &Transaction) {
+        let mut cursor = ChangeCursor::new(&transaction.changes);
+        self.root = self.root.take().map(|root| apply_node(root, &mut cursor));
+    }
+
+    /// Invokes `f` with every leaf [TextNode] in the rope, in document order.
+    pub fn for_each_leaf<'a>(&'a self, f: &mut impl FnMut(&'a TextNode)) {
+        if let Some(root) = self.root.as_deref() {
+            root.for_each_leaf(f);
+        }
+    }
+
+    /// The rope's entire text, as UTF-16 code points, concatenated in document order.
+    pub fn flatten(&self) -> Vec<u16> {
+        let mut out = Vec::with_capacity(self.metrics().units);
+        self.for_each_leaf(&mut |node| out.extend_from_slice(node.code_points()));
+        out
+    }
+}
+
+/// The [Boundary] spanned by a rope with the given aggregate [Metrics].
+pub(crate) fn boundary_from_metrics(metrics: Metrics) -> Boundary {
+    Boundary {
+        origin: Position::default(),
+        width: metrics.max_width,
+        height: metrics.lines,
+    }
+}
+
+/// Applies `cursor`'s changes to `node`'s subtree, skipping whole subtrees the current op fully
+/// retains rather than visiting their leaves.
+fn apply_node(node: Box<RopeNode>, cursor: &mut ChangeCursor) -> Box<RopeNode> {
+    let metrics = node.metrics();
+    if cursor.fully_retains(metrics.units) {
+        cursor.skip_retain(metrics.units);
+        return node;
+    }
+
+    match *node {
+        RopeNode::Leaf(leaf) => match cursor.apply_to_node(&leaf) {
+            Some(code_points) => Box::new(split_if_needed(leaf.rebuild(String::from_utf16_lossy(&code_points)))),
+            None => Box::new(RopeNode::Leaf(leaf)),
+        },
+        RopeNode::Internal { left, right, .. } => {
+            let left = apply_node(left, cursor);
+            let right = apply_node(right, cursor);
+            Box::new(merge_if_needed(left, right))
+        }
+    }
+}
+
+/// Wraps `node` as a single [`RopeNode::Leaf`] if it fits within [`MAX_LEAF_UNITS`], otherwise
+/// splits it at its middle line and recurses on each half. Splits only fall on line boundaries, so
+/// a node (or half) with fewer than two lines stays a single leaf even when it is over
+/// [`MAX_LEAF_UNITS`] code units.
+fn split_if_needed(node: TextNode) -> RopeNode {
+    if node.code_points().len() <= MAX_LEAF_UNITS || node.line_count() < 2 {
+        return RopeNode::Leaf(node);
+    }
+
+    let split_unit = node.line_start_unit(node.line_count() / 2);
+    let left = TextNode::new_delimitered(String::from_utf16_lossy(&node.code_points()[..split_unit]));
+    let right = TextNode::new_delimitered(String::from_utf16_lossy(&node.code_points()[split_unit..]));
+
+    make_internal(Box::new(split_if_needed(left)), Box::new(split_if_needed(right)))
+}
+
+/// Collapses `left` and `right` back into a single leaf when both are leaves small enough to fit
+/// together within [`MAX_LEAF_UNITS`], otherwise keeps them as a normal [`RopeNode::Internal`].
+fn merge_if_needed(left: Box<RopeNode>, right: Box<RopeNode>) -> RopeNode {
+    if let (RopeNode::Leaf(a), RopeNode::Leaf(b)) = (left.as_ref(), right.as_ref()) {
+        if a.code_points().len() + b.code_points().len() <= MAX_LEAF_UNITS {
+            let mut merged = a.code_points().to_vec();
+            merged.extend_from_slice(b.code_points());
+            return RopeNode::Leaf(a.rebuild(String::from_utf16_lossy(&merged)));
+        }
+    }
+
+    make_internal(left, right)
+}
+
+/// Builds an [`RopeNode::Internal`] over `left` and `right`, combining their metrics and deriving
+/// the subtree's height from theirs.
+fn make_internal(left: Box<RopeNode>, right: Box<RopeNode>) -> RopeNode {
+    let metrics = left.metrics().combine(right.metrics());
+    let height = left.height().max(right.height()) + 1;
+    RopeNode::Internal { metrics, height, left, right }
+}
+
+/// Joins `left` and `right` into a single subtree, keeping the AVL-style invariant that every
+/// node's children differ in height by at most one. If the two sides already differ by at most
+/// one level, this is just [`make_internal`]; otherwise it descends along the taller side's
+/// inner spine, joins the far end against the shorter side, and rotates on the way back up,
+/// fixing at most one level of imbalance per stack frame. [`Rope::push`] calls this on every
+/// insertion, which bounds the whole tree's depth to O(log n) in the number of leaves instead of
+/// letting it grow into a spine.
+fn join(left: Box<RopeNode>, right: Box<RopeNode>) -> RopeNode {
+    let (left_height, right_height) = (left.height(), right.height());
+
+    if left_height > right_height + 1 {
+        let RopeNode::Internal { left: ll, right: lr, .. } = *left else {
+            unreachable!("a leaf's height is 0, so it can't be taller than `right_height + 1`");
+        };
+        rebalance(ll, Box::new(join(lr, right)))
+    } else if right_height > left_height + 1 {
+        let RopeNode::Internal { left: rl, right: rr, .. } = *right else {
+            unreachable!("a leaf's height is 0, so it can't be taller than `left_height + 1`");
+        };
+        rebalance(Box::new(join(left, rl)), rr)
+    } else {
+        make_internal(left, right)
+    }
+}
+
+/// Restores the AVL-style invariant for `left`/`right` that differ in height by at most two,
+/// the postcondition [`join`]'s recursive descent guarantees at each level it unwinds through.
+/// Rotates once (or twice, for the zig-zag case) toward whichever side is heavier.
+fn rebalance(left: Box<RopeNode>, right: Box<RopeNode>) -> RopeNode {
+    let (left_height, right_height) = (left.height(), right.height());
+
+    if left_height > right_height + 1 {
+        let RopeNode::Internal { left: ll, right: lr, .. } = *left else {
+            unreachable!("a leaf's height is 0, so it can't be taller than `right_height + 1`");
+        };
+        if ll.height() >= lr.height() {
+            make_internal(ll, Box::new(make_internal(lr, right)))
+        } else {
+            let RopeNode::Internal { left: lrl, right: lrr, .. } = *lr else {
+                unreachable!("`lr` is taller than `ll`, so it can't be a leaf (height 0)");
+            };
+            make_internal(Box::new(make_internal(ll, lrl)), Box::new(make_internal(lrr, right)))
+        }
+    } else if right_height > left_height + 1 {
+        let RopeNode::Internal { left: rl, right: rr, .. } = *right else {
+            unreachable!("a leaf's height is 0, so it can't be taller than `left_height + 1`");
+        };
+        if rr.height() >= rl.height() {
+            make_internal(Box::new(make_internal(left, rl)), rr)
+        } else {
+            let RopeNode::Internal { left: rll, right: rlr, .. } = *rl else {
+                unreachable!("`rl` is taller than `rr`, so it can't be a leaf (height 0)");
+            };
+            make_internal(Box::new(make_internal(left, rll)), Box::new(make_internal(rlr, rr)))
+        }
+    } else {
+        make_internal(left, right)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn leaf_sizes(rope: &Rope) -> Vec<usize> {
+        let mut sizes = Vec::new();
+        rope.for_each_leaf(&mut |node| sizes.push(node.code_points().len()));
+        sizes
+    }
+
+    #[test]
+    fn push_recursively_splits_oversized_nodes_into_leaves_within_the_limit() {
+        let line = "x".repeat(10) + "\n";
+        let text = line.repeat((MAX_LEAF_UNITS / line.len()) * 5);
+        let mut rope = Rope::new();
+        rope.push(TextNode::new_delimitered(text));
+
+        let sizes = leaf_sizes(&rope);
+        assert!(sizes.len() > 2, "a node this oversized should have been split more than once: {sizes:?}");
+        assert!(
+            sizes.iter().all(|&size| size <= MAX_LEAF_UNITS),
+            "every leaf must stay within MAX_LEAF_UNITS: {sizes:?}"
+        );
+    }
+
+    #[test]
+    fn apply_merges_leaves_back_together_once_they_shrink() {
+        let line = "x".repeat(10) + "\n";
+        let text = line.repeat((MAX_LEAF_UNITS / line.len()) * 5);
+        let mut rope = Rope::new();
+        rope.push(TextNode::new_delimitered(text));
+        assert!(leaf_sizes(&rope).len() > 1);
+
+        let total = rope.metrics().units;
+        let keep = 20;
+        rope.apply(&Transaction::new().retain(keep).delete(total - keep));
+
+        assert_eq!(
+            leaf_sizes(&rope),
+            vec![keep],
+            "shrinking back under MAX_LEAF_UNITS should re-merge the rope into a single leaf"
+        );
+    }
+}
@@ -1,4 +1,8 @@
-use crate::text::Position;
+use std::ops::RangeInclusive;
+
+use smallvec::{smallvec, SmallVec};
+
+use crate::text::{Position, TextNode};
 
 /// An abstraction for Cursor-like types. This uniform api, even in its current naive state, provides a level of
 /// convenience for navigating over a collection of items.
@@ -29,6 +33,137 @@ pub trait Cursor {
     /// ```
     fn seek(&mut self, to: &Position) -> Option<Self::Value>;
 
-    /// The current position of the cursor relative to the collection of items its navigating over. 
+    /// The current position of the cursor relative to the collection of items its navigating over.
     fn position(&self) -> &Position;
 }
+
+/// A span of text between an `anchor`, where the selection started, and a `head`, the end
+/// currently being moved. Dragging the head while the anchor stays put grows or shrinks the
+/// selection; an empty range (`anchor == head`) behaves as a plain cursor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Range {
+    pub anchor: Position,
+    pub head: Position,
+}
+
+impl Range {
+    /// Constructs a range spanning from `anchor` to `head`.
+    pub fn new(anchor: Position, head: Position) -> Self {
+        Self { anchor, head }
+    }
+
+    /// The earlier of `anchor`/`head` in document order.
+    pub fn from(&self) -> Position {
+        self.anchor.min(self.head)
+    }
+
+    /// The later of `anchor`/`head` in document order.
+    pub fn to(&self) -> Position {
+        self.anchor.max(self.head)
+    }
+
+    /// True when `anchor` and `head` coincide, i.e. this range selects no text.
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+
+    /// The inclusive span of lines this range touches within `node`, clamped to its line count.
+    pub fn line_range(&self, node: &TextNode) -> RangeInclusive<usize> {
+        let max_line = node.line_count().saturating_sub(1);
+        self.from().line.min(max_line)..=self.to().line.min(max_line)
+    }
+
+    /// Moves `head` to `to`, leaving `anchor` in place, growing or shrinking the selection.
+    pub fn extend(self, to: Position) -> Self {
+        Self { head: to, ..self }
+    }
+
+    /// Swaps `anchor` and `head`, reversing the range's direction while covering the same span.
+    pub fn flip(self) -> Self {
+        Self {
+            anchor: self.head,
+            head: self.anchor,
+        }
+    }
+}
+
+/// One or more [Range]s over a [TextNode], supporting multi-cursor editing. Ranges are kept
+/// sorted by start position and non-overlapping; pushing or mapping a range that overlaps another
+/// merges them so cursors never collide. The `primary` range is the one plain navigation and
+/// typing apply to.
+#[derive(Clone, Debug)]
+pub struct Selection {
+    ranges: SmallVec<[Range; 1]>,
+    primary: usize,
+}
+
+impl Selection {
+    /// A selection containing just `range`, marked primary.
+    pub fn single(range: Range) -> Self {
+        Self {
+            ranges: smallvec![range],
+            primary: 0,
+        }
+    }
+
+    /// Adds `range` to the selection, merging it with any range it overlaps.
+    pub fn push(&mut self, range: Range) {
+        self.ranges.push(range);
+        self.normalize();
+    }
+
+    /// The primary range, the one plain navigation and typing apply to.
+    pub fn primary(&self) -> &Range {
+        &self.ranges[self.primary]
+    }
+
+    /// Every range in the selection, sorted by start position.
+    pub fn ranges(&self) -> &[Range] {
+        &self.ranges
+    }
+
+    /// Collapses the primary range to a single point at `pos`, as plain (non-extending) cursor
+    /// movement does.
+    pub fn collapse_primary_to(&mut self, pos: Position) {
+        self.ranges[self.primary] = Range::new(pos, pos);
+        self.normalize();
+    }
+
+    /// Applies `f` to every range in the selection, e.g. to carry ranges forward across an edit,
+    /// then re-normalizes in case the edit caused ranges to collide.
+    pub fn map(&mut self, mut f: impl FnMut(Range) -> Range) {
+        for range in self.ranges.iter_mut() {
+            *range = f(*range);
+        }
+        self.normalize();
+    }
+
+    /// Sorts ranges by their start position and merges any that overlap, so cursors never
+    /// collide, while keeping track of which merged range the primary one ended up in.
+    fn normalize(&mut self) {
+        if self.ranges.len() <= 1 {
+            return;
+        }
+
+        let mut indexed: SmallVec<[(usize, Range); 1]> =
+            self.ranges.iter().copied().enumerate().collect();
+        indexed.sort_by_key(|(_, range)| range.from());
+
+        let mut merged: SmallVec<[Range; 1]> = SmallVec::new();
+        let mut primary = 0;
+        for (original_index, range) in indexed {
+            match merged.last_mut() {
+                Some(last) if last.to() >= range.from() => {
+                    *last = Range::new(last.from().min(range.from()), last.to().max(range.to()));
+                }
+                _ => merged.push(range),
+            }
+            if original_index == self.primary {
+                primary = merged.len() - 1;
+            }
+        }
+
+        self.ranges = merged;
+        self.primary = primary;
+    }
+}
@@ -5,7 +5,13 @@ use std::{
     sync::atomic::{AtomicUsize, Ordering},
 };
 
-use crate::{buffer::Boundary, cursor::Cursor};
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+use unicode_width::UnicodeWidthChar;
+
+use crate::{
+    buffer::Boundary,
+    cursor::{Cursor, Range, Selection},
+};
 
 /// A value used to uniquely identify a node. The current default provider generates new ids statically, incrementing by 1 on each call, in order to avoid collision. There is no api in place,
 /// as of yet, to allow for user-defined providers however, this may change in the future.
@@ -32,33 +38,35 @@ pub struct TextNode {
 impl TextNode {
     /// The standardized, and currently recommended, approach for constructing a new node. The current expectations are
     /// the following:
-    /// - The passed-in text is delimitered with a [Newline](https://en.wikipedia.org/wiki/Newline) character sequence either in
-    /// `CRLF` (Windows-style) or `LF` (Unix-like) form.
+    /// - The passed-in text is delimitered with any of the [LineEnding] sequences this crate recognizes, most commonly `CRLF`
+    /// (Windows-style) or `LF` (Unix-like).
     pub fn new_delimitered(text: impl AsRef<str>) -> Self {
         let mut width_descriminator = 0;
+        let mut byte_cursor = 0usize;
         let code_points: Vec<u16> = text.as_ref().encode_utf16().collect();
 
-        let line_endings: Vec<TextRange> =
-            code_points
-                .iter()
-                .enumerate()
-                .fold(Vec::new(), |mut acc, (index, code)| {
-                    if *code == b'\n'.into() {
-                        let prev_lf = if acc.is_empty() {
-                            0
-                        } else {
-                            acc.last().unwrap().end + 1
-                        };
-
-                        let range: TextRange = (prev_lf..=index).into();
-                        let width = range.units();
-                        if width > width_descriminator {
-                            width_descriminator = width;
-                        }
-                        acc.push(range);
-                    }
-                    acc
-                });
+        let mut line_endings: Vec<TextRange> = Vec::new();
+        let mut line_start = 0usize;
+        let mut idx = 0usize;
+        while idx < code_points.len() {
+            let Some((ending, len)) = LineEnding::from_code_units(&code_points[idx..]) else {
+                idx += 1;
+                continue;
+            };
+
+            let mut range: TextRange = (line_start..=idx + len - 1).into();
+            range.ending = ending;
+            range.width = line_visual_width(&code_points, &range);
+            if range.width > width_descriminator {
+                width_descriminator = range.width;
+            }
+            byte_cursor += utf16_slice_byte_len(&code_points[range.start..=range.end]);
+            range.byte_end = byte_cursor;
+            line_endings.push(range);
+
+            idx += len;
+            line_start = idx;
+        }
 
         let dimensions = Boundary {
             origin: Position::default(),
@@ -79,6 +87,103 @@ impl TextNode {
             .flat_map(|range| String::from_utf16(&self.code_points[range.start..=range.end]))
             .collect()
     }
+
+    /// The raw UTF-16 code points backing this node.
+    pub(crate) fn code_points(&self) -> &[u16] {
+        &self.code_points
+    }
+
+    /// The number of lines in this node.
+    pub fn line_count(&self) -> usize {
+        self.line_endings.len()
+    }
+
+    /// The total UTF-8 byte length of this node's text.
+    pub(crate) fn byte_len(&self) -> usize {
+        self.line_endings.last().map(|range| range.byte_end).unwrap_or(0)
+    }
+
+    /// The code-unit index the given line starts at, or `code_points.len()` if `line` is past
+    /// the last line.
+    pub(crate) fn line_start_unit(&self, line: usize) -> usize {
+        line.checked_sub(1)
+            .and_then(|prev| self.line_endings.get(prev))
+            .map(|prev| prev.end + 1)
+            .unwrap_or(0)
+    }
+
+    /// The [LineEnding] terminating the given line index, or `None` if it's out of range.
+    pub fn line_ending(&self, line: usize) -> Option<LineEnding> {
+        self.line_endings.get(line).map(|range| range.ending)
+    }
+
+    /// Rebuilds this node's content from `text`, recomputing its line endings and dimensions,
+    /// while preserving its identity so it keeps its place in a [std::collections::BTreeSet]
+    /// ordered by id.
+    pub(crate) fn rebuild(&self, text: impl AsRef<str>) -> Self {
+        let mut node = Self::new_delimitered(text);
+        node.id = self.id;
+        node
+    }
+
+    /// The on-screen terminal width, in cells, of the given line index. Wide characters (e.g.
+    /// CJK ideographs, fullwidth forms, most emoji) count for two cells, zero-width and
+    /// combining characters count for zero, control characters count for zero, and everything
+    /// else counts for one. The line ending itself is not counted. Returns `0` for
+    /// an out-of-range line.
+    pub fn visual_width(&self, line: usize) -> usize {
+        self.line_endings
+            .get(line)
+            .map(|range| range.width)
+            .unwrap_or(0)
+    }
+
+    /// Converts a UTF-8 byte offset into the text into a [Position]. The column is measured in
+    /// grapheme clusters, the same unit [TextCursor] navigates by, so `byte -> position -> byte`
+    /// round-trips stably.
+    ///
+    /// A byte exactly on a line boundary belongs to the following line's column `0`. A byte at
+    /// or past the end of the text resolves to the last line's one-past-end column.
+    pub fn position_of_byte(&self, byte: usize) -> Position {
+        let line = self
+            .line_endings
+            .partition_point(|range| range.byte_end <= byte)
+            .min(self.line_endings.len().saturating_sub(1));
+
+        let Some(range) = self.line_endings.get(line) else {
+            return Position::default();
+        };
+        let line_start_byte = line
+            .checked_sub(1)
+            .and_then(|prev| self.line_endings.get(prev))
+            .map(|prev| prev.byte_end)
+            .unwrap_or(0);
+        let byte_in_line = byte.saturating_sub(line_start_byte);
+        let line_code_points = &self.code_points[range.start..=range.end];
+        let column = column_of_byte_in_line(line_code_points, byte_in_line);
+
+        Position { line, column }
+    }
+
+    /// Converts a [Position] into a UTF-8 byte offset into the text, the inverse of
+    /// [`TextNode::position_of_byte`].
+    pub fn byte_of_position(&self, position: &Position) -> usize {
+        let Some(range) = self.line_endings.get(position.line) else {
+            return self
+                .line_endings
+                .last()
+                .map(|range| range.byte_end)
+                .unwrap_or(0);
+        };
+        let line_start_byte = position
+            .line
+            .checked_sub(1)
+            .and_then(|prev| self.line_endings.get(prev))
+            .map(|prev| prev.byte_end)
+            .unwrap_or(0);
+        let line_code_points = &self.code_points[range.start..=range.end];
+        line_start_byte + byte_of_column_in_line(line_code_points, position.column)
+    }
 }
 
 impl PartialOrd for TextNode {
@@ -101,37 +206,61 @@ impl Debug for TextNode {
 }
 
 pub struct TextCursor<'node> {
-    position: Position,
+    selection: Selection,
     node: &'node TextNode,
 }
 
 impl<'node> TextCursor<'node> {
     pub fn new(node: &'node TextNode) -> Self {
         Self {
-            position: Position::default(),
+            selection: Selection::single(Range::default()),
             node,
         }
     }
+
+    /// The full multi-cursor selection this cursor is driving. Plain navigation via [`seek`](Cursor::seek)
+    /// only ever moves the primary range; use this to read or extend the others.
+    pub fn selection(&self) -> &Selection {
+        &self.selection
+    }
+
+    /// Mutable access to the selection, for callers that need to push additional ranges (e.g.
+    /// adding a cursor) or extend an existing one into a selection.
+    pub fn selection_mut(&mut self) -> &mut Selection {
+        &mut self.selection
+    }
 }
 
 impl<'node> Cursor for TextCursor<'node> {
     type Value = &'node [u16];
 
     fn seek(&mut self, to: &Position) -> Option<Self::Value> {
-        let src = self.position.column;
-        let slice = &self.node.code_points[src..to.column];
-        self.position = *to;
+        let code_points = self.node.code_points();
+        // `Position::column` counts grapheme clusters, not code units, so the current column has
+        // to be walked forward by that many grapheme boundaries before it can index `code_points`.
+        let current_column = self.selection.primary().head.column;
+        let src = nth_next_grapheme_boundary(code_points, 0, current_column);
+        // From there, step only the `to.column` delta rather than re-walking from `0`: forward
+        // via `nth_next_grapheme_boundary`, backward via its counterpart.
+        let dst = if to.column >= current_column {
+            nth_next_grapheme_boundary(code_points, src, to.column - current_column)
+        } else {
+            nth_prev_grapheme_boundary(code_points, src, current_column - to.column)
+        };
+        let slice = &code_points[src.min(dst)..src.max(dst)];
+        self.selection
+            .collapse_primary_to(Position { column: to.column, ..*to });
         Some(slice)
     }
 
     fn position(&self) -> &Position {
-        &self.position
+        &self.selection.primary().head
     }
 }
 
 /// The line and column values of a [TextNode] within a buffer. These values are analogous
 /// to a pair of x and y coordinates on a 2d grid.
-#[derive(Clone, Copy, Debug, Default, Eq, Ord)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Position {
     pub line: usize,
     pub column: usize,
@@ -162,18 +291,6 @@ impl Hash for Position {
     }
 }
 
-impl PartialOrd for Position {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.hash().partial_cmp(&other.hash())
-    }
-}
-
-impl PartialEq for Position {
-    fn eq(&self, other: &Self) -> bool {
-        self.hash().eq(&other.hash())
-    }
-}
-
 impl Add<(usize, usize)> for Position {
     type Output = Self;
 
@@ -210,22 +327,225 @@ impl From<(usize, usize)> for Position {
 pub(crate) struct TextRange {
     pub start: usize,
     pub end: usize,
+    /// The on-screen terminal width, in cells, this line occupies. Populated at construction
+    /// time by [`line_visual_width`].
+    pub width: usize,
+    /// The cumulative UTF-8 byte offset, from the start of the node's text, marking the end of
+    /// this line (inclusive of its line ending). Populated at construction time and consulted by
+    /// [`TextNode::position_of_byte`].
+    pub byte_end: usize,
+    /// Which line-ending sequence terminates this line, preserved so the original sequence can
+    /// be reproduced on round-trip instead of being normalized to a single kind.
+    pub ending: LineEnding,
 }
 
 impl TextRange {
     /// The total amount of units this range covers. A single unit, in the current application usage, can be seen as
-    /// a [`grapheme`](https://unicode.org/glossary/#grapheme).
-    pub fn units(&self) -> usize {
-        self.end - self.start
+    /// a [`grapheme`](https://unicode.org/glossary/#grapheme). `code_points` is the slice this range indexes into,
+    /// i.e. the owning [TextNode]'s code points.
+    pub fn units(&self, code_points: &[u16]) -> usize {
+        let slice = &code_points[self.start..=self.end];
+        let walker = GraphemeWalker::new(slice);
+        let mut idx = 0;
+        let mut count = 0;
+        while idx < slice.len() {
+            idx = walker.next(idx);
+            count += 1;
+        }
+        count
     }
 }
 
+/// Decodes a UTF-16 code-unit slice into its UTF-8 projection, alongside a table mapping each
+/// char's starting code-unit index to the byte offset it begins at in the decoded string. Code
+/// units that fall inside a surrogate pair have no entry, since a position there can never be a
+/// grapheme-cluster boundary.
+fn utf16_to_grapheme_source(slice: &[u16]) -> (String, Vec<(usize, usize)>) {
+    let mut text = String::with_capacity(slice.len());
+    let mut char_starts = Vec::with_capacity(slice.len() + 1);
+    let mut unit = 0;
+    for ch in char::decode_utf16(slice.iter().copied()) {
+        let ch = ch.unwrap_or(char::REPLACEMENT_CHARACTER);
+        char_starts.push((unit, text.len()));
+        text.push(ch);
+        unit += ch.len_utf16();
+    }
+    char_starts.push((unit, text.len()));
+    (text, char_starts)
+}
+
+/// Maps a code-unit index to the byte offset of the char starting at-or-before it.
+fn unit_to_byte(char_starts: &[(usize, usize)], unit: usize) -> usize {
+    match char_starts.binary_search_by_key(&unit, |&(u, _)| u) {
+        Ok(i) => char_starts[i].1,
+        Err(i) => char_starts[i.saturating_sub(1)].1,
+    }
+}
+
+/// Maps a decoded byte offset back to its code-unit index.
+fn byte_to_unit(char_starts: &[(usize, usize)], byte: usize) -> usize {
+    char_starts
+        .binary_search_by_key(&byte, |&(_, b)| b)
+        .map(|i| char_starts[i].0)
+        .unwrap_or_else(|_| char_starts.last().copied().unwrap_or((0, 0)).0)
+}
+
+/// A single decode of a UTF-16 slice into its UTF-8 projection and code-unit/byte lookup table,
+/// reused across every boundary query an operation needs. Walking `n` boundaries via
+/// [`GraphemeWalker::next`]/[`prev`](GraphemeWalker::prev) in a loop pays for
+/// [`utf16_to_grapheme_source`]'s allocation once, rather than once per step the way repeatedly
+/// calling a single-shot `slice -> boundary` function would.
+struct GraphemeWalker {
+    text: String,
+    char_starts: Vec<(usize, usize)>,
+}
+
+impl GraphemeWalker {
+    fn new(slice: &[u16]) -> Self {
+        let (text, char_starts) = utf16_to_grapheme_source(slice);
+        Self { text, char_starts }
+    }
+
+    /// The code-unit length of the slice this walker was built from.
+    fn len(&self) -> usize {
+        self.char_starts.last().map_or(0, |&(unit, _)| unit)
+    }
+
+    /// The code-unit index of the next extended grapheme cluster boundary after `idx`, or
+    /// [`len`](Self::len) if `idx` is already within the final cluster.
+    fn next(&self, idx: usize) -> usize {
+        if idx >= self.len() {
+            return self.len();
+        }
+        let byte = unit_to_byte(&self.char_starts, idx);
+        let mut cursor = GraphemeCursor::new(byte, self.text.len(), true);
+        match cursor.next_boundary(&self.text, 0) {
+            Ok(Some(next)) => byte_to_unit(&self.char_starts, next),
+            _ => self.len(),
+        }
+    }
+
+    /// The code-unit index of the previous extended grapheme cluster boundary before `idx`, or
+    /// `0` if `idx` is already within the first cluster.
+    fn prev(&self, idx: usize) -> usize {
+        if idx == 0 {
+            return 0;
+        }
+        let byte = unit_to_byte(&self.char_starts, idx.min(self.len()));
+        let mut cursor = GraphemeCursor::new(byte, self.text.len(), true);
+        match cursor.prev_boundary(&self.text, 0) {
+            Ok(Some(prev)) => byte_to_unit(&self.char_starts, prev),
+            _ => 0,
+        }
+    }
+
+    /// Walks forward `n` grapheme cluster boundaries starting from `idx`, clamping at the end.
+    fn nth_next(&self, idx: usize, n: usize) -> usize {
+        (0..n).fold(idx, |acc, _| self.next(acc))
+    }
+
+    /// Walks backward `n` grapheme cluster boundaries starting from `idx`, clamping at the start.
+    fn nth_prev(&self, idx: usize, n: usize) -> usize {
+        (0..n).fold(idx, |acc, _| self.prev(acc))
+    }
+}
+
+/// Walks forward `n` grapheme cluster boundaries starting from `idx`, clamping at the end of
+/// `slice`. Decodes `slice` once up front and reuses it for every step, rather than paying for a
+/// fresh decode per boundary the way folding a single-shot `slice -> boundary` function `n` times
+/// would.
+pub(crate) fn nth_next_grapheme_boundary(slice: &[u16], idx: usize, n: usize) -> usize {
+    GraphemeWalker::new(slice).nth_next(idx, n)
+}
+
+/// Walks backward `n` grapheme cluster boundaries starting from `idx`, clamping at the start of
+/// `slice`. Decodes `slice` once up front and reuses it for every step, rather than paying for a
+/// fresh decode per boundary the way folding a single-shot `slice -> boundary` function `n` times
+/// would.
+pub(crate) fn nth_prev_grapheme_boundary(slice: &[u16], idx: usize, n: usize) -> usize {
+    GraphemeWalker::new(slice).nth_prev(idx, n)
+}
+
 impl From<RangeInclusive<usize>> for TextRange {
     fn from(value: RangeInclusive<usize>) -> Self {
         Self {
             start: *value.start(),
             end: *value.end(),
+            width: 0,
+            byte_end: 0,
+            ending: LineEnding::default(),
+        }
+    }
+}
+
+/// The total UTF-8 byte length a UTF-16 code-unit slice decodes to.
+fn utf16_slice_byte_len(slice: &[u16]) -> usize {
+    char::decode_utf16(slice.iter().copied())
+        .map(|ch| ch.unwrap_or(char::REPLACEMENT_CHARACTER).len_utf8())
+        .sum()
+}
+
+/// Given a single line's code points and a UTF-8 byte offset within that line, returns the
+/// grapheme-cluster column the byte offset falls on.
+fn column_of_byte_in_line(line_code_points: &[u16], byte_in_line: usize) -> usize {
+    let mut byte = 0;
+    let mut unit = 0;
+    for ch in char::decode_utf16(line_code_points.iter().copied()) {
+        if byte >= byte_in_line {
+            break;
+        }
+        let ch = ch.unwrap_or(char::REPLACEMENT_CHARACTER);
+        byte += ch.len_utf8();
+        unit += ch.len_utf16();
+    }
+
+    let walker = GraphemeWalker::new(line_code_points);
+    let mut idx = 0;
+    let mut column = 0;
+    while idx < unit {
+        idx = walker.next(idx);
+        column += 1;
+    }
+    column
+}
+
+/// Given a single line's code points and a grapheme-cluster column, returns the UTF-8 byte
+/// offset within that line the column starts at.
+fn byte_of_column_in_line(line_code_points: &[u16], column: usize) -> usize {
+    let unit = nth_next_grapheme_boundary(line_code_points, 0, column);
+    let mut byte = 0;
+    let mut seen = 0;
+    for ch in char::decode_utf16(line_code_points.iter().copied()) {
+        if seen >= unit {
+            break;
         }
+        let ch = ch.unwrap_or(char::REPLACEMENT_CHARACTER);
+        byte += ch.len_utf8();
+        seen += ch.len_utf16();
+    }
+    byte
+}
+
+/// Sums the terminal cell width of every extended grapheme cluster on `range` within
+/// `code_points`, excluding the line ending itself: wide characters (CJK, fullwidth, most emoji)
+/// count for two cells, zero-width and combining characters count for zero, control characters
+/// are explicitly treated as zero, and everything else counts for one.
+fn line_visual_width(code_points: &[u16], range: &TextRange) -> usize {
+    let content_end = range.end + 1 - range.ending.as_utf16().1;
+    let slice = &code_points[range.start..content_end];
+    let (text, _) = utf16_to_grapheme_source(slice);
+    text.graphemes(true)
+        .map(|grapheme| grapheme.chars().map(grapheme_char_width).sum::<usize>())
+        .sum()
+}
+
+/// The terminal cell width of a single `char`, treating control characters as zero-width
+/// regardless of what [`unicode_width`] reports for them.
+fn grapheme_char_width(c: char) -> usize {
+    if c.is_control() {
+        0
+    } else {
+        c.width().unwrap_or(0)
     }
 }
 
@@ -236,22 +556,61 @@ pub fn assert_utf8_empty(text: impl AsRef<[u8]>) -> bool {
         .all(char::is_whitespace)
 }
 
-/// A [Newline](https://en.wikipedia.org/wiki/Newline) type supported by the current text processors set in place.
-#[derive(Default, Debug)]
+/// A [Newline](https://en.wikipedia.org/wiki/Newline) type supported by the current text processors set in place. Covers every
+/// line-ending sequence recognized by the [Unicode line breaking algorithm](https://www.unicode.org/reports/tr14/), not just
+/// `CRLF`/`LF`, so text containing a lone `CR`, `NEL`, vertical tab, form feed, or the Unicode line/paragraph separators round-trips
+/// without those being silently folded into the surrounding line.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum LineEnding {
     #[default]
     Crlf,
     Lf,
+    Cr,
+    Nel,
+    VerticalTab,
+    FormFeed,
+    LineSeparator,
+    ParagraphSeparator,
 }
 
 impl LineEnding {
-    pub fn as_utf16(&self) -> [u16; 2] {
-        let _bytes: [u16; 2] = [0u16; 2];
-        let encoded = match self {
-            Self::Crlf => [13u16, 10u16],
-            Self::Lf => [0u16, 10u16],
-        };
-        encoded
+    /// Every variant, in the match priority [`from_code_units`](Self::from_code_units) scans with. `Crlf` must be
+    /// checked before `Cr` so a `\r\n` pair isn't read as a lone `\r`.
+    const VARIANTS: [LineEnding; 8] = [
+        Self::Crlf,
+        Self::Lf,
+        Self::Cr,
+        Self::Nel,
+        Self::VerticalTab,
+        Self::FormFeed,
+        Self::LineSeparator,
+        Self::ParagraphSeparator,
+    ];
+
+    /// This ending's UTF-16 code units, paired with how many of `encoded`'s slots are meaningful.
+    /// Single-unit endings only occupy index `0`; the encoding is length-tagged so those don't
+    /// carry a spurious leading zero the way the old fixed `[u16; 2]` encoding did.
+    pub fn as_utf16(&self) -> ([u16; 2], usize) {
+        match self {
+            Self::Crlf => ([13, 10], 2),
+            Self::Lf => ([10, 0], 1),
+            Self::Cr => ([13, 0], 1),
+            Self::Nel => ([0x0085, 0], 1),
+            Self::VerticalTab => ([0x000B, 0], 1),
+            Self::FormFeed => ([0x000C, 0], 1),
+            Self::LineSeparator => ([0x2028, 0], 1),
+            Self::ParagraphSeparator => ([0x2029, 0], 1),
+        }
+    }
+
+    /// Attempts to match a line-ending sequence at the start of `units`, returning the matched
+    /// variant together with how many code units it consumed, or `None` if `units` doesn't start
+    /// with one.
+    pub fn from_code_units(units: &[u16]) -> Option<(Self, usize)> {
+        Self::VARIANTS.into_iter().find_map(|ending| {
+            let (encoded, len) = ending.as_utf16();
+            (units.len() >= len && units[..len] == encoded[..len]).then_some((ending, len))
+        })
     }
 }
 
@@ -260,23 +619,20 @@ impl ToString for LineEnding {
         match self {
             Self::Crlf => "\r\n".into(),
             Self::Lf => "\n".into(),
+            Self::Cr => "\r".into(),
+            Self::Nel => "\u{0085}".into(),
+            Self::VerticalTab => "\u{000B}".into(),
+            Self::FormFeed => "\u{000C}".into(),
+            Self::LineSeparator => "\u{2028}".into(),
+            Self::ParagraphSeparator => "\u{2029}".into(),
         }
     }
 }
 
-impl PartialEq for LineEnding {
-    fn eq(&self, other: &Self) -> bool {
-        self.as_utf16().eq(&other.as_utf16())
-    }
-}
-
 impl PartialEq<[u16]> for LineEnding {
-    
     fn eq(&self, other: &[u16]) -> bool {
-        match self {
-            Self::Crlf => other[0] == 13u16 && other[1] == 10u16,
-            Self::Lf => other[1] == 10u16,
-        }
+        let (encoded, len) = self.as_utf16();
+        other.len() == len && other[..len] == encoded[..len]
     }
 }
 
@@ -295,4 +651,23 @@ something interesting.
         buffer.push(node);
         println!("{}", buffer);
     }
+
+    #[test]
+    fn position_of_byte_and_byte_of_position_round_trip() {
+        let text = "héllo wörld\nsécond liñe\n";
+        let node = crate::text::TextNode::new_delimitered(text);
+
+        for (byte, _) in text.char_indices() {
+            let position = node.position_of_byte(byte);
+            assert_eq!(
+                node.byte_of_position(&position),
+                byte,
+                "byte {byte} should round-trip through {position:?}"
+            );
+        }
+
+        let end = text.len();
+        let position = node.position_of_byte(end);
+        assert_eq!(node.byte_of_position(&position), end);
+    }
 }